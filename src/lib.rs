@@ -4,8 +4,9 @@
 //! obtaining ownership, borrowing and dereferencing. Further subtraits expose more specialized
 //! functionality which is nonetheless applicable to any sort of smart pointer.
 //!
-//! These additional, specialized traits are being added to this crate as consumers need them. If
-//! you would like to see traits for additional features, e.g. conversion with raw pointers,
+//! These additional, specialized traits are being added to this crate as consumers need them.
+//! Conversion with raw pointers is covered by `RawPointer`. If you would like to see traits for
+//! additional features, e.g.
 //! [efficient borrows](https://docs.rs/triomphe/0.1.1/triomphe/struct.ArcBorrow.html),
 //! [pointers directly to the data](https://docs.rs/triomphe/0.1.1/triomphe/struct.OffsetArc.html)
 //! or [thin DST pointers](https://docs.rs/triomphe/0.1.1/triomphe/struct.ThinArc.html), open an
@@ -18,6 +19,8 @@ extern crate maybe_std as base;
 use base::borrow::{Borrow, BorrowMut};
 use base::ops::{Deref, DerefMut};
 use base::fmt::Pointer;
+use base::pin::Pin;
+use base::mem::MaybeUninit;
 
 /// The minimum amount of functionality common to all smart pointer types sharing ownership of a
 /// value of type `T`. This trait only grants immutable access to the stored value, see
@@ -27,8 +30,10 @@ use base::fmt::Pointer;
 /// Note that most of the actual pointer functionality comes from the prerequisite traits.
 ///
 /// Also note that this trait omits some functionality because it can only be expressed with
-/// higher-kinded types, such as working with uninitialized memory, conversions to slices,
-/// downcasting of `Any` values.
+/// higher-kinded types, such as conversions to slices or downcasting of `Any` values. Working
+/// with uninitialized memory, which used to be thought to belong to this category, is covered by
+/// `SmartPointerUninit` instead: it turned out to need nothing more than an ordinary associated
+/// type.
 pub trait SmartPointer<T: ?Sized>: Sized + Clone + AsRef<T> + Borrow<T> + Deref<Target = T> + Pointer
 // + CoerceUnsized<Ptr<U>> + DispatchFromDyn<Rc<U>> where T: Unsize<U>, U: ?Sized
 {
@@ -39,10 +44,52 @@ pub trait SmartPointer<T: ?Sized>: Sized + Clone + AsRef<T> + Borrow<T> + Deref<
     ///
     /// This fails if there are other smart pointers wrapping the exact same value.
     fn try_unwrap(this: Self) -> Result<T, Self> where T: Sized;
+
+    /// Pin the wrapped value.
+    ///
+    /// This is safe because `StableAddress` already guarantees that the address `deref` resolves
+    /// to never changes for the lifetime of the value, and that moving `this` does not move the
+    /// pointee. If `Self: SmartPointerMut`, the resulting `Pin<Self>` also grants mutable
+    /// projection through `Pin::as_mut`.
+    fn as_pin(this: Self) -> Pin<Self> where Self: StableAddress {
+        unsafe { Pin::new_unchecked(this) }
+    }
+
+    /// Check whether two smart pointers point to the same allocation.
+    ///
+    /// For `?Sized` values (fat pointers), only the address of the data is compared, not the
+    /// vtable pointer or slice length.
+    fn ptr_eq(a: &Self, b: &Self) -> bool {
+        let a: &T = AsRef::as_ref(a);
+        let b: &T = AsRef::as_ref(b);
+        a as *const T as *const () == b as *const T as *const ()
+    }
+
+    /// The number of `SmartPointer`s (not counting weak references) currently sharing ownership
+    /// of the wrapped value.
+    fn strong_count(this: &Self) -> usize;
+
+    /// The number of weak references (see `Downgrade`/`WeakPointer`) currently pointing at the
+    /// allocation backing this value.
+    ///
+    /// Defaults to `0` for implementors which do not support weak references.
+    ///
+    /// Safety-relevant: `IntoMut::can_make_mut`'s default implementation relies on this returning
+    /// the true count of outstanding `WeakPointer`s whenever `Self: Downgrade`, since a live weak
+    /// reference can `upgrade()` into a second strong pointer at any time. Any implementor of
+    /// `Downgrade` must override this method accordingly; leaving it at the default `0` would make
+    /// `can_make_mut`/`into_mut`/`get_mut` falsely claim unique ownership.
+    fn weak_count(_this: &Self) -> usize {
+        0
+    }
 }
 
 /// A `SmartPointer` which beyond immutable access to the wrapped value also provides mutable
 /// access via the `AsMut`, `BorrowMut` and `DerefMut` traits.
+///
+/// Note there is no separate `as_pin_mut`: `SmartPointer::as_pin` already produces a `Pin<Self>`
+/// for any `Self: StableAddress`, and once `Self: DerefMut` that `Pin` grants mutable projection
+/// through `Pin::as_mut` regardless of which constructor produced it.
 pub trait SmartPointerMut<T: ?Sized>: SmartPointer<T> + AsMut<T> + BorrowMut<T> + DerefMut<Target = T> {}
 
 /// A `SmartPointer` which might grant mutable access, depending on run-time checks.
@@ -50,7 +97,13 @@ pub trait IntoMut<T: ?Sized>: SmartPointer<T> {
     type MutablePointer: SmartPointerMut<T> + Into<Self>;
 
     /// Check whether converting into a mutable version would succeed.
-    fn can_make_mut(this: &Self) -> bool;
+    ///
+    /// The default implementation follows the principle that mutable access requires unique
+    /// ownership: it succeeds exactly when there is a single strong reference and no weak
+    /// references to the wrapped value.
+    fn can_make_mut(this: &Self) -> bool {
+        SmartPointer::strong_count(this) == 1 && SmartPointer::weak_count(this) == 0
+    }
 
     /// Convert into a mutable version without performing runtime checks for upholding any
     /// invariants.
@@ -83,16 +136,386 @@ pub trait IntoMut<T: ?Sized>: SmartPointer<T> {
     }
 }
 
-// Might become trait:
-//
-// // fn as_ptr(this: &Self) -> *const T;
-// fn into_raw(this: Self) -> *const T;
-// unsafe fn from_raw(ptr: *const T) -> Self;
+/// A `SmartPointer` which can be converted to and from a raw pointer, e.g. for use in FFI or in
+/// other unsafe abstractions.
+pub trait RawPointer<T: ?Sized>: SmartPointer<T> {
+    /// Obtain a raw pointer to the wrapped value without giving up ownership.
+    ///
+    /// The returned pointer remains valid for as long as `this` (or any other smart pointer
+    /// sharing ownership of the same value) is still alive.
+    fn as_ptr(this: &Self) -> *const T;
+
+    /// Consume the smart pointer, returning a raw pointer to the wrapped value.
+    ///
+    /// This leaks ownership: the destructor is not run and, for reference-counted pointers, the
+    /// strong count is not decremented. Use `from_raw` to reclaim the ownership released here.
+    fn into_raw(this: Self) -> *const T;
+
+    /// Reconstitute a smart pointer from a raw pointer previously obtained via `into_raw`.
+    ///
+    /// Safety: `ptr` must have been obtained by a call to `into_raw` on an instance of this exact
+    /// implementor, and `from_raw` must be called at most once per such `ptr`. For
+    /// reference-counted implementors, this reclaims exactly the one strong count that `into_raw`
+    /// released.
+    unsafe fn from_raw(ptr: *const T) -> Self;
+}
+
+/// A `SmartPointer` which can produce a weak reference to the same value, i.e. a reference which
+/// keeps the allocation alive but does not prevent the value itself from being dropped.
+///
+/// Implementors of this trait must also override `SmartPointer::weak_count` to report the true
+/// number of outstanding `Weak` pointers; the default of `0` would otherwise make
+/// `IntoMut::can_make_mut` unsound (it would claim unique ownership while a weak pointer could
+/// still `upgrade()` into a second strong pointer, aliasing a value that is borrowed mutably).
+pub trait Downgrade<T: ?Sized>: SmartPointer<T> {
+    /// The weak pointer type produced by `downgrade`.
+    type Weak: WeakPointer<T, Strong = Self>;
+
+    /// Create a new weak reference to the same value.
+    fn downgrade(this: &Self) -> Self::Weak;
+}
+
+/// A weak reference produced by `Downgrade::downgrade`.
+///
+/// Holding a `WeakPointer` keeps the allocation (control block) of the pointee alive, but does
+/// not keep the value itself alive. `upgrade` succeeds if and only if at least one strong pointer
+/// to the value still exists.
+pub trait WeakPointer<T: ?Sized>: Sized + Clone {
+    /// The strong pointer type that this weak pointer can be upgraded to.
+    type Strong: Downgrade<T>;
+
+    /// Try to upgrade this weak pointer into a strong one.
+    ///
+    /// Returns `None` once all strong owners of the value have been dropped.
+    fn upgrade(this: &Self) -> Option<Self::Strong>;
+
+    /// Create a new weak pointer which does not point to any value and never upgrades.
+    fn new() -> Self;
+}
+
+/// A marker trait for pointers whose pointee never changes address while the pointer is alive,
+/// which is exactly the guarantee `Pin` relies on.
+///
+/// Safety: implementors must guarantee that (1) `deref` always resolves to the same address for
+/// as long as the value lives, and (2) moving the smart pointer itself never moves the pointee,
+/// i.e. the pointer is only a handle to a separately allocated value. Heap-allocating pointers
+/// such as `Rc`, `Arc` and `Box` satisfy this; pointers which store small values inline (and thus
+/// move the value together with the pointer) must not implement this trait.
+pub unsafe trait StableAddress: Deref {}
+
+/// A `SmartPointer` which can allocate storage for a `T` without initializing it, and later
+/// convert that storage into a fully initialized pointer.
+///
+/// This lets callers allocate first and initialize the value in place afterwards, avoiding a
+/// move of a potentially large `T` through the stack.
+pub trait SmartPointerUninit<T>: SmartPointer<T> {
+    /// The pointer type used to hold the uninitialized value.
+    type Uninit: SmartPointerMut<MaybeUninit<T>>;
+
+    /// Allocate storage for a `T` without initializing it.
+    fn new_uninit() -> Self::Uninit;
+
+    /// Convert a pointer to a (by now fully initialized) `MaybeUninit<T>` into a pointer to `T`.
+    ///
+    /// Safety: the `MaybeUninit<T>` wrapped by `uninit` must have been fully initialized with a
+    /// valid `T` before calling this.
+    unsafe fn assume_init(uninit: Self::Uninit) -> Self;
+}
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
+
+    use std::boxed::Box;
+    use std::rc::{Rc, Weak};
+
+    use super::*;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    /// A toy shared pointer on top of `Rc`, implementing the traits of this crate so that their
+    /// provided methods (`ptr_eq`, `can_make_mut`, `as_pin`) and the raw, weak and uninitialized
+    /// conversions can be exercised against a real weak-reference subsystem.
+    struct Toy<T> {
+        rc: Rc<T>,
+    }
+
+    impl<T> Clone for Toy<T> {
+        fn clone(&self) -> Self {
+            Toy { rc: Rc::clone(&self.rc) }
+        }
+    }
+
+    impl<T> Pointer for Toy<T> {
+        fn fmt(&self, f: &mut base::fmt::Formatter) -> base::fmt::Result {
+            Pointer::fmt(&self.rc, f)
+        }
+    }
+
+    impl<T> AsRef<T> for Toy<T> {
+        fn as_ref(&self) -> &T {
+            &self.rc
+        }
+    }
+
+    impl<T> Borrow<T> for Toy<T> {
+        fn borrow(&self) -> &T {
+            &self.rc
+        }
+    }
+
+    impl<T> Deref for Toy<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            &self.rc
+        }
+    }
+
+    impl<T> SmartPointer<T> for Toy<T> {
+        fn new(t: T) -> Self {
+            Toy { rc: Rc::new(t) }
+        }
+
+        fn try_unwrap(this: Self) -> Result<T, Self> {
+            Rc::try_unwrap(this.rc).map_err(|rc| Toy { rc })
+        }
+
+        fn strong_count(this: &Self) -> usize {
+            Rc::strong_count(&this.rc)
+        }
+
+        // `Toy` implements `Downgrade`, so per that trait's contract this must report the real
+        // weak count rather than rely on the `0` default.
+        fn weak_count(this: &Self) -> usize {
+            Rc::weak_count(&this.rc)
+        }
+    }
+
+    /// The unique counterpart of `Toy`, used as `Toy`'s `IntoMut::MutablePointer` and, wrapping a
+    /// `MaybeUninit`, as `Toy`'s `SmartPointerUninit::Uninit`.
+    struct ToyMut<T>(Box<T>);
+
+    impl<T: Clone> Clone for ToyMut<T> {
+        fn clone(&self) -> Self {
+            ToyMut(Box::new((*self.0).clone()))
+        }
+    }
+
+    impl<T> Pointer for ToyMut<T> {
+        fn fmt(&self, f: &mut base::fmt::Formatter) -> base::fmt::Result {
+            Pointer::fmt(&self.0, f)
+        }
+    }
+
+    impl<T> AsRef<T> for ToyMut<T> {
+        fn as_ref(&self) -> &T {
+            &self.0
+        }
+    }
+
+    impl<T> AsMut<T> for ToyMut<T> {
+        fn as_mut(&mut self) -> &mut T {
+            &mut self.0
+        }
+    }
+
+    impl<T> Borrow<T> for ToyMut<T> {
+        fn borrow(&self) -> &T {
+            &self.0
+        }
+    }
+
+    impl<T> BorrowMut<T> for ToyMut<T> {
+        fn borrow_mut(&mut self) -> &mut T {
+            &mut self.0
+        }
+    }
+
+    impl<T> Deref for ToyMut<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            &self.0
+        }
+    }
+
+    impl<T> DerefMut for ToyMut<T> {
+        fn deref_mut(&mut self) -> &mut T {
+            &mut self.0
+        }
+    }
+
+    impl<T: Clone> SmartPointer<T> for ToyMut<T> {
+        fn new(t: T) -> Self {
+            ToyMut(Box::new(t))
+        }
+
+        fn try_unwrap(this: Self) -> Result<T, Self> {
+            Ok(*this.0)
+        }
+
+        fn strong_count(_this: &Self) -> usize {
+            1
+        }
+    }
+
+    impl<T: Clone> SmartPointerMut<T> for ToyMut<T> {}
+
+    impl<T: Clone> From<ToyMut<T>> for Toy<T> {
+        fn from(unique: ToyMut<T>) -> Self {
+            Toy::new(*unique.0)
+        }
+    }
+
+    impl<T: Clone> IntoMut<T> for Toy<T> {
+        type MutablePointer = ToyMut<T>;
+
+        // `can_make_mut` deliberately uses the provided default so that the tests below exercise
+        // exactly the formula documented on `IntoMut`.
+
+        unsafe fn into_mut_unchecked(this: Self) -> Self::MutablePointer {
+            ToyMut(Box::new(Rc::try_unwrap(this.rc).ok().expect("can_make_mut upheld uniqueness")))
+        }
+
+        unsafe fn get_mut_unchecked(this: &Self) -> &mut T {
+            &mut *(Rc::as_ptr(&this.rc) as *mut T)
+        }
+    }
+
+    impl<T> RawPointer<T> for Toy<T> {
+        fn as_ptr(this: &Self) -> *const T {
+            Rc::as_ptr(&this.rc)
+        }
+
+        fn into_raw(this: Self) -> *const T {
+            Rc::into_raw(this.rc)
+        }
+
+        unsafe fn from_raw(ptr: *const T) -> Self {
+            Toy { rc: Rc::from_raw(ptr) }
+        }
+    }
+
+    // Safe: `Toy` is only a handle to a separately heap-allocated `Rc`, so its address is stable
+    // across moves of the `Toy` itself, and `deref` always resolves into that same allocation.
+    unsafe impl<T> StableAddress for Toy<T> {}
+
+    /// The weak counterpart of `Toy`, produced by `Downgrade::downgrade`.
+    struct ToyWeak<T>(Weak<T>);
+
+    impl<T> Clone for ToyWeak<T> {
+        fn clone(&self) -> Self {
+            ToyWeak(self.0.clone())
+        }
+    }
+
+    impl<T> WeakPointer<T> for ToyWeak<T> {
+        type Strong = Toy<T>;
+
+        fn upgrade(this: &Self) -> Option<Toy<T>> {
+            this.0.upgrade().map(|rc| Toy { rc })
+        }
+
+        fn new() -> Self {
+            ToyWeak(Weak::new())
+        }
+    }
+
+    impl<T> Downgrade<T> for Toy<T> {
+        type Weak = ToyWeak<T>;
+
+        fn downgrade(this: &Self) -> ToyWeak<T> {
+            ToyWeak(Rc::downgrade(&this.rc))
+        }
+    }
+
+    impl<T: Copy> SmartPointerUninit<T> for Toy<T> {
+        type Uninit = ToyMut<MaybeUninit<T>>;
+
+        fn new_uninit() -> Self::Uninit {
+            ToyMut(Box::new(MaybeUninit::uninit()))
+        }
+
+        unsafe fn assume_init(uninit: Self::Uninit) -> Self {
+            Toy::new((*uninit.0).assume_init())
+        }
+    }
+
+    #[test]
+    fn ptr_eq_compares_addresses_not_values() {
+        let a = Toy::new(1u32);
+        let b = a.clone();
+        let c = Toy::new(1u32);
+
+        assert!(SmartPointer::ptr_eq(&a, &b));
+        assert!(!SmartPointer::ptr_eq(&a, &c));
+    }
+
+    #[test]
+    fn can_make_mut_default_requires_unique_strong_and_no_weak() {
+        let unique = Toy::new(5i32);
+        assert!(IntoMut::can_make_mut(&unique));
+
+        let shared = unique.clone();
+        assert!(!IntoMut::can_make_mut(&unique));
+        drop(shared);
+        assert!(IntoMut::can_make_mut(&unique));
+
+        let with_weak = Toy::new(7i32);
+        let weak = Downgrade::downgrade(&with_weak);
+        assert!(!IntoMut::can_make_mut(&with_weak));
+        drop(weak);
+        assert!(IntoMut::can_make_mut(&with_weak));
+    }
+
+    #[test]
+    fn weak_pointer_upgrades_iff_a_strong_pointer_is_still_alive() {
+        let strong = Toy::new(9i32);
+        let weak = Downgrade::downgrade(&strong);
+
+        let upgraded = WeakPointer::upgrade(&weak).expect("strong pointer is still alive");
+        assert_eq!(*upgraded, 9);
+
+        drop(upgraded);
+        drop(strong);
+        assert!(WeakPointer::upgrade(&weak).is_none());
+
+        let dangling: ToyWeak<i32> = WeakPointer::new();
+        assert!(WeakPointer::upgrade(&dangling).is_none());
+    }
+
+    #[test]
+    fn raw_pointer_round_trips_ownership() {
+        let original = Toy::new(42u32);
+        assert_eq!(unsafe { *RawPointer::as_ptr(&original) }, 42);
+
+        let raw = RawPointer::into_raw(original);
+        let restored: Toy<u32> = unsafe { RawPointer::from_raw(raw) };
+        assert_eq!(*restored, 42);
+        assert_eq!(SmartPointer::strong_count(&restored), 1);
+    }
+
+    #[test]
+    fn as_pin_preserves_the_address_and_the_value() {
+        let toy = Toy::new(11i32);
+        let addr_before = &*SmartPointer::as_pin(toy.clone()) as *const i32;
+
+        let pinned = SmartPointer::as_pin(toy);
+        assert_eq!(*pinned, 11);
+        assert_eq!(&*pinned as *const i32, addr_before);
+    }
+
+    #[test]
+    fn new_uninit_then_assume_init_round_trips_the_value() {
+        let mut uninit = <Toy<i32> as SmartPointerUninit<i32>>::new_uninit();
+        unsafe {
+            uninit.as_mut_ptr().write(77);
+        }
+        let toy = unsafe { <Toy<i32> as SmartPointerUninit<i32>>::assume_init(uninit) };
+        assert_eq!(*toy, 77);
+    }
 }